@@ -0,0 +1,110 @@
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde::Serialize;
+
+/// Selects how values are turned into bytes before being handed to a
+/// backend's storage engine.
+///
+/// Each backend used to hard-wire its own format (`ReDbStore` always used
+/// MessagePack via `rmp_serde`, `FSStore` and `LocalStorageStore` always
+/// used JSON via `serde_json`). Giving each store a `Codec` through its
+/// `with_codec` builder instead (`StoreConfig` does not carry one) lets the
+/// same value round-trip identically regardless of which backend
+/// reads it back, e.g. when migrating a WASM `LocalStorage` build (JSON) to
+/// a native `redb` build (MessagePack), or switching away from `rmp_serde`
+/// for formats that don't self-describe struct fields as maps.
+///
+/// This is a closed enum rather than an open trait: the three formats below
+/// are the only ones any backend needs to dispatch on, `Codec` has to stay
+/// `Copy` so every backend can hold one inline (and hand it to a
+/// [`WriteBatch`](crate::redb_store::WriteBatch)/[`Namespace`](crate::redb_store::Namespace)
+/// without boxing), and a `match` over three variants is simpler than a
+/// `dyn`-safe trait object for a fixed, closed set of formats. A trait
+/// would only pay off if this crate needed third-party codec authors to
+/// plug in arbitrary formats, which isn't the goal here.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Compact, schema-less MessagePack encoding via `rmp_serde`
+    #[default]
+    MessagePack,
+    /// Self-describing JSON encoding via `serde_json`
+    Json,
+    /// Compact binary encoding via `bincode`
+    Bincode,
+}
+
+/// Errors that can occur while encoding or decoding a value with a [`Codec`]
+#[derive(thiserror::Error, Debug)]
+pub enum CodecError {
+    /// Error from the `rmp_serde` MessagePack codec
+    #[error("MessagePack error")]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+    /// Error from the `rmp_serde` MessagePack codec
+    #[error("MessagePack error")]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+    /// Error from the `serde_json` JSON codec
+    #[error("JSON error")]
+    Json(#[from] serde_json::Error),
+    /// Error from the `bincode` codec
+    #[error("bincode error")]
+    Bincode(#[from] bincode::Error),
+    /// [`Codec::decode_seed`] was called on a codec other than
+    /// [`Codec::MessagePack`]
+    #[error("decode_seed only supports Codec::MessagePack, got {0:?}")]
+    UnsupportedSeedCodec(Codec),
+}
+
+impl Codec {
+    /// Serialize `value` to bytes using the selected codec
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Codec::MessagePack => {
+                let mut serializer = rmp_serde::Serializer::new(Vec::new()).with_struct_map();
+                value.serialize(&mut serializer)?;
+                Ok(serializer.into_inner())
+            }
+            Codec::Json => Ok(serde_json::to_vec(value)?),
+            Codec::Bincode => Ok(bincode::serialize(value)?),
+        }
+    }
+
+    /// Deserialize a value of type `T` from `bytes` using the selected codec
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, CodecError> {
+        match self {
+            Codec::MessagePack => Ok(rmp_serde::from_slice(bytes)?),
+            Codec::Json => Ok(serde_json::from_slice(bytes)?),
+            Codec::Bincode => Ok(bincode::deserialize(bytes)?),
+        }
+    }
+
+    /// Deserialize `bytes` into a stateful [`DeserializeSeed`] using the
+    /// selected codec, for callers that need to thread context through
+    /// deserialization instead of producing a plain `T: DeserializeOwned`.
+    ///
+    /// Only [`Codec::MessagePack`] is supported, returning
+    /// [`CodecError::UnsupportedSeedCodec`] otherwise: `serde_json`'s and
+    /// `bincode`'s `Deserializer`s don't implement `serde::Deserializer`
+    /// generically enough over the input lifetime to dispatch on `self`
+    /// inside one generic helper the way [`decode`](Self::decode) does.
+    ///
+    /// Note for callers: this only composes with code that already holds
+    /// `bytes` and `seed` in the same generic scope that produces the final
+    /// return value (e.g. a plain function taking `&'de [u8]`). It can't be
+    /// called *through* a trait method like [`StoreImpl::get_with`](crate::StoreImpl::get_with)
+    /// whose return type's lifetime is tied to `&self` rather than to this
+    /// call's own `'de` — rustc has no way to prove those two lifetimes'
+    /// `<T as DeserializeSeed<'_>>::Value` projections equal across the
+    /// extra function-call boundary, so `ReDbStore::get_with` implements the
+    /// same MessagePack-only match inline instead of delegating here.
+    pub fn decode_seed<'de, T>(self, bytes: &'de [u8], seed: T) -> Result<T::Value, CodecError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self {
+            Codec::MessagePack => {
+                let mut deserializer = rmp_serde::decode::Deserializer::new(bytes);
+                seed.deserialize(&mut deserializer).map_err(CodecError::from)
+            }
+            Codec::Json | Codec::Bincode => Err(CodecError::UnsupportedSeedCodec(self)),
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use std::{fs, io};
+use std::io::Write;
 use std::path::Path;
 use serde::de::DeserializeSeed;
+use crate::codec::{Codec, CodecError};
+use crate::migration::SCHEMA_VERSION_KEY;
 use crate::{Location, PlatformDefault, StoreImpl};
 
 #[derive(Debug, Default)]
 pub struct FSStore {
     path: String,
+    codec: Codec,
 }
 
 pub use FSStore as InnerStore;
@@ -14,16 +18,16 @@ pub use FSStore as InnerStore;
 pub enum GetError {
     #[error("No value found for the given key")]
     NotFound,
-    #[error("error deserializing json")]
-    Json(#[from] serde_json::Error),
+    #[error("error decoding value")]
+    Codec(#[from] CodecError),
     #[error("Error opening file")]
     File(#[from] io::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum SetError {
-    #[error("Error serializing as json")]
-    Json(#[from] serde_json::Error),
+    #[error("error encoding value")]
+    Codec(#[from] CodecError),
     #[error("Error opening file")]
     File(#[from] io::Error),
 }
@@ -35,12 +39,170 @@ impl FSStore {
             .expect("Failed to create directory to init key value store");
         Self {
             path: dir_path.as_path().to_str().unwrap_or("./").to_string(),
+            codec: Codec::Json,
         }
     }
 
+    /// Use `codec` instead of the default [`Codec::Json`] to encode and
+    /// decode values in this store.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    fn format_key(&self, key: &str) -> String {
+        format!("{}/{}", self.path, key)
+    }
+}
+
+/// A staged batch of key/value writes, flushed to disk together by
+/// [`FSStore::set_batch`] with a single directory sync instead of one per
+/// key.
+pub struct WriteBatch {
+    codec: Codec,
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl WriteBatch {
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Stage a serialized value for `key`. Nothing is written until the batch
+    /// is passed to [`FSStore::set_batch`].
+    pub fn set<T: serde::Serialize>(
+        &mut self,
+        key: impl Into<String>,
+        value: &T,
+    ) -> Result<(), SetError> {
+        let bytes = self.codec.encode(value)?;
+        self.entries.push((key.into(), bytes));
+        Ok(())
+    }
+
+    /// Stage a string value for `key`.
+    pub fn set_string(&mut self, key: impl Into<String>, value: &str) -> Result<(), SetError> {
+        let bytes = self.codec.encode(&value)?;
+        self.entries.push((key.into(), bytes));
+        Ok(())
+    }
+}
+
+impl FSStore {
+    /// Start a [`WriteBatch`] using this store's configured codec.
+    pub fn batch(&self) -> WriteBatch {
+        WriteBatch::new(self.codec)
+    }
+
+    /// Write every entry staged in `batch`. Each entry is written to a temp
+    /// file in the store directory, fsynced, then atomically renamed over
+    /// the key's real path, so a crash mid-batch never leaves a key
+    /// truncated or partially written — it's either still at its pre-batch
+    /// contents or fully at its new ones. The store directory is then
+    /// synced once at the end so the renames themselves are durable, rather
+    /// than syncing once per key.
+    ///
+    /// This does not make the batch atomic as a whole: a crash between two
+    /// entries' renames can leave some of this batch's keys written and
+    /// others not. Only [`ReDbStore::set_batch`](crate::redb_store::ReDbStore::set_batch)
+    /// commits a batch all-or-nothing.
+    pub fn set_batch(&mut self, batch: WriteBatch) -> Result<(), SetError> {
+        for (key, bytes) in &batch.entries {
+            let path = self.format_key(key);
+            let tmp_path = format!("{path}.tmp");
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(bytes)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &path)?;
+        }
+        fs::File::open(&self.path)?.sync_all()?;
+        Ok(())
+    }
+}
+
+/// A handle to one of a store's named sub-stores, each backed by its own
+/// subdirectory of the store's directory. Obtained from
+/// [`FSStore::namespace`]; lets a game keep e.g. `"settings"`, `"saves"`,
+/// and `"leaderboard"` in logically separate key spaces instead of sharing
+/// the store's single flat directory.
+pub struct Namespace {
+    path: String,
+    codec: Codec,
+}
+
+impl Namespace {
     fn format_key(&self, key: &str) -> String {
         format!("{}/{}", self.path, key)
     }
+
+    /// Serialize and store the value under `key` in this namespace
+    pub fn set<T: serde::Serialize>(&self, key: &str, value: &T) -> Result<(), SetError> {
+        let bytes = self.codec.encode(value)?;
+        fs::write(self.format_key(key), bytes)?;
+        Ok(())
+    }
+
+    /// Get the value for `key` in this namespace. Returns
+    /// `Err(GetError::NotFound)` if the key does not exist.
+    pub fn get<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<T, GetError> {
+        let data = match fs::read(self.format_key(key)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(GetError::NotFound),
+            Err(err) => return Err(err.into()),
+        };
+        self.codec.decode(&data).map_err(Into::into)
+    }
+
+    /// Append `value` to the list of values stored under `key`, keeping
+    /// every previously appended value in insertion order. Use
+    /// [`get_all`](Self::get_all) to read them back.
+    pub fn append<T: serde::Serialize + serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), SetError> {
+        let path = self.format_key(key);
+        let mut items: Vec<Vec<u8>> = match fs::read(&path) {
+            Ok(data) => self.codec.decode(&data)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        items.push(self.codec.encode(value)?);
+        let bytes = self.codec.encode(&items)?;
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Read back every value appended under `key` via [`append`](Self::append),
+    /// in insertion order. Returns an empty `Vec` if `key` has never been
+    /// appended to.
+    pub fn get_all<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Vec<T>, GetError> {
+        let items: Vec<Vec<u8>> = match fs::read(self.format_key(key)) {
+            Ok(data) => self.codec.decode(&data)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        items
+            .iter()
+            .map(|bytes| self.codec.decode(bytes).map_err(Into::into))
+            .collect()
+    }
+}
+
+impl FSStore {
+    /// Open a handle to the named sub-store, creating its backing
+    /// subdirectory if it doesn't exist yet. See [`Namespace`].
+    pub fn namespace(&self, name: &str) -> Namespace {
+        let path = format!("{}/{}", self.path, name);
+        fs::create_dir_all(&path).expect("Failed to create directory for namespace");
+        Namespace {
+            path,
+            codec: self.codec,
+        }
+    }
 }
 
 impl StoreImpl for FSStore {
@@ -48,16 +210,16 @@ impl StoreImpl for FSStore {
     type SetError = SetError;
 
     fn set_string(&mut self, key: &str, value: &str) -> Result<(), SetError> {
-        let json = serde_json::to_string(value)?;
+        let bytes = self.codec.encode(&value)?;
         let key = self.format_key(key);
-        fs::write(key,json.as_bytes())?;
+        fs::write(key, bytes)?;
         Ok(())
     }
 
     fn get<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<T, GetError> {
         let key = self.format_key(key);
-        let data = fs::read_to_string(key)?;
-        let value: T = serde_json::from_str(data.as_str())?;
+        let data = fs::read(key)?;
+        let value: T = self.codec.decode(&data)?;
         Ok(value)
     }
 
@@ -66,9 +228,9 @@ impl StoreImpl for FSStore {
     }
 
     fn set<T: serde::Serialize>(&mut self, key: &str, value: &T) -> Result<(), SetError> {
-        let json = serde_json::to_string(value)?;
+        let bytes = self.codec.encode(value)?;
         let key = self.format_key(key);
-        fs::write(key,json.as_bytes())?;
+        fs::write(key, bytes)?;
         Ok(())
     }
 
@@ -85,4 +247,43 @@ impl StoreImpl for FSStore {
         }
         Ok(())
     }
+
+    fn keys(&self) -> Result<Vec<String>, Self::GetError> {
+        let mut keys: Vec<String> = fs::read_dir(self.path.as_str())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|key| key != SCHEMA_VERSION_KEY)
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+impl FSStore {
+    /// Every key in the store whose name begins with `prefix`, in sorted
+    /// key order. Unlike [`ReDbStore::keys_with_prefix`](crate::redb_store::ReDbStore::keys_with_prefix),
+    /// flat files have no native range query to push the filter into, so
+    /// this still scans every file in the store directory.
+    pub fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>, GetError> {
+        let mut keys = self.keys()?;
+        keys.retain(|key| key.starts_with(prefix));
+        Ok(keys)
+    }
+
+    /// Every `(key, value)` pair whose key falls in `range`, decoded with
+    /// the store's configured codec, in sorted key order.
+    pub fn range<T: serde::de::DeserializeOwned>(
+        &self,
+        range: impl std::ops::RangeBounds<String>,
+    ) -> Result<Vec<(String, T)>, GetError> {
+        let mut keys = self.keys()?;
+        keys.retain(|key| range.contains(key));
+        keys.into_iter()
+            .map(|key| {
+                let value: T = self.get(&key)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
 }
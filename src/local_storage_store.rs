@@ -1,8 +1,11 @@
+use crate::codec::{Codec, CodecError};
+use crate::migration::SCHEMA_VERSION_KEY;
 use crate::{StoreConfig, StoreImpl};
 
 #[derive(Debug, Default)]
 pub struct LocalStorageStore {
     prefix: String,
+    codec: Codec,
 }
 
 pub use LocalStorageStore as InnerStore;
@@ -13,14 +16,22 @@ pub enum GetError {
     NotFound,
     #[error("JavaScript error from getItem")]
     GetItem(wasm_bindgen::JsValue),
+    #[error("error decoding value")]
+    Codec(#[from] CodecError),
+    /// The stored string wasn't valid hex for the configured binary codec.
+    /// This would only happen if something outside this crate wrote to the
+    /// key, since [`LocalStorageStore::set`]/[`set_string`](LocalStorageStore::set_string)
+    /// always hex-encode binary codecs' output before storing it.
+    #[error("stored value is not valid hex for the configured codec")]
+    InvalidHex,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum SetError {
     #[error("JavaScript error from setItem")]
     SetItem(wasm_bindgen::JsValue),
-    #[error("Error serializing as json")]
-    Json(#[from] serde_json::Error),
+    #[error("error encoding value")]
+    Codec(#[from] CodecError),
 }
 
 impl LocalStorageStore {
@@ -43,12 +54,51 @@ impl LocalStorageStore {
                 Some(qualifier) => format!("{qualifier}.{organization}.{application}"),
                 None => format!("{organization}.{application}"),
             },
+            codec: Codec::Json,
         }
     }
 
+    /// Use `codec` instead of the default [`Codec::Json`] to encode and
+    /// decode values in this store.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
     fn format_key(&self, key: &str) -> String {
         format!("{}{}", self.prefix, key)
     }
+
+    /// Turn encoded bytes into a string fit to pass to `Storage::set_item`.
+    /// `Codec::Json` already encodes to valid UTF-8, so it's stored as-is
+    /// for readability in devtools; the binary codecs are hex-encoded so
+    /// they can't be mangled by `LocalStorage`'s string-only API.
+    fn bytes_to_storage(&self, bytes: Vec<u8>) -> String {
+        match self.codec {
+            Codec::Json => String::from_utf8(bytes).expect("Codec::Json always encodes UTF-8"),
+            Codec::MessagePack | Codec::Bincode => {
+                bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+            }
+        }
+    }
+
+    /// The inverse of [`bytes_to_storage`](Self::bytes_to_storage).
+    fn bytes_from_storage(&self, stored: &str) -> Result<Vec<u8>, GetError> {
+        match self.codec {
+            Codec::Json => Ok(stored.as_bytes().to_vec()),
+            Codec::MessagePack | Codec::Bincode => {
+                if !stored.is_ascii() || stored.len() % 2 != 0 {
+                    return Err(GetError::InvalidHex);
+                }
+                (0..stored.len())
+                    .step_by(2)
+                    .map(|i| {
+                        u8::from_str_radix(&stored[i..i + 2], 16).map_err(|_| GetError::InvalidHex)
+                    })
+                    .collect()
+            }
+        }
+    }
 }
 
 impl StoreImpl for LocalStorageStore {
@@ -56,10 +106,11 @@ impl StoreImpl for LocalStorageStore {
     type SetError = SetError;
 
     fn set_string(&mut self, key: &str, value: &str) -> Result<(), SetError> {
-        let json = serde_json::to_string(value)?;
+        let bytes = self.codec.encode(&value)?;
+        let stored = self.bytes_to_storage(bytes);
         let storage = self.storage();
         let key = self.format_key(key);
-        storage.set_item(&key, &json).map_err(SetError::SetItem)?;
+        storage.set_item(&key, &stored).map_err(SetError::SetItem)?;
         Ok(())
     }
 
@@ -67,16 +118,62 @@ impl StoreImpl for LocalStorageStore {
         let storage = self.storage();
         let key = self.format_key(key);
         let entry = storage.get_item(&key).map_err(GetError::GetItem)?;
-        let json = entry.as_ref().ok_or(GetError::NotFound)?;
-        let value: T = serde_json::from_str(json).unwrap();
+        let stored = entry.as_ref().ok_or(GetError::NotFound)?;
+        let bytes = self.bytes_from_storage(stored)?;
+        let value: T = self.codec.decode(&bytes)?;
         Ok(value)
     }
 
     fn set<T: serde::Serialize>(&mut self, key: &str, value: &T) -> Result<(), SetError> {
-        let json = serde_json::to_string(value)?;
+        let bytes = self.codec.encode(value)?;
+        let stored = self.bytes_to_storage(bytes);
         let storage = self.storage();
         let key = self.format_key(key);
-        storage.set_item(&key, &json).map_err(SetError::SetItem)?;
+        storage.set_item(&key, &stored).map_err(SetError::SetItem)?;
         Ok(())
     }
+
+    fn keys(&self) -> Result<Vec<String>, Self::GetError> {
+        let storage = self.storage();
+        let len = storage.length().map_err(GetError::GetItem)?;
+        let mut keys = Vec::new();
+        for i in 0..len {
+            if let Some(key) = storage.key(i).map_err(GetError::GetItem)? {
+                if let Some(key) = key.strip_prefix(&self.prefix) {
+                    if key != SCHEMA_VERSION_KEY {
+                        keys.push(key.to_string());
+                    }
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+impl LocalStorageStore {
+    /// Every key in the store whose name begins with `prefix`, in sorted
+    /// key order. `LocalStorage` has no native range query, so this still
+    /// scans every key in the browser's storage for this store's prefix.
+    pub fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>, GetError> {
+        let mut keys = self.keys()?;
+        keys.retain(|key| key.starts_with(prefix));
+        Ok(keys)
+    }
+
+    /// Every `(key, value)` pair whose key falls in `range`, decoded with
+    /// the store's configured codec, in sorted key order.
+    pub fn range<T: serde::de::DeserializeOwned>(
+        &self,
+        range: impl std::ops::RangeBounds<String>,
+    ) -> Result<Vec<(String, T)>, GetError> {
+        let mut keys = self.keys()?;
+        keys.retain(|key| range.contains(key));
+        keys.into_iter()
+            .map(|key| {
+                let value: T = self.get(&key)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
 }
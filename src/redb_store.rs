@@ -1,12 +1,25 @@
+use crate::codec::{Codec, CodecError};
+use crate::migration::SCHEMA_VERSION_KEY;
 use crate::{Location, StoreImpl};
-use redb::{Database, ReadableTable, TableDefinition};
+use redb::{Database, ReadableTable, TableDefinition, TableHandle};
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{Archive, CheckBytes};
 use serde::de::DeserializeSeed;
 use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashSet;
 use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Mutex;
 use tracing::info;
 
 pub struct ReDbStore {
     db: Database,
+    codec: Codec,
+    /// Names already leaked to `'static` by a prior [`Self::namespace`] call,
+    /// so calling it again with the same name reuses the existing leaked
+    /// string instead of leaking a fresh one every time.
+    namespace_names: Mutex<HashSet<&'static str>>,
 }
 impl Debug for ReDbStore {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -31,11 +44,28 @@ pub enum GetError {
     /// The value for the given key was not found
     #[error("No value found for the given key")]
     NotFound,
-    /// Error when deserializing the value
-    #[error("MessagePack deserialization error")]
-    MessagePack(#[from] rmp_serde::decode::Error),
+    /// Error when decoding the value with the store's configured [`Codec`]
+    #[error("Codec decode error")]
+    Codec(#[from] CodecError),
+    /// An internal database error from the `redb` crate, e.g. while opening
+    /// a backup file for restore
+    #[error("ReDbDatabaseError error")]
+    ReDbDatabaseError(#[from] redb::DatabaseError),
+    /// The stored bytes did not pass `rkyv` archive validation
+    #[error("rkyv archive validation failed")]
+    RkyvValidation,
+    /// [`StoreImpl::get_with`] was called on a store configured with a
+    /// [`Codec`] other than [`Codec::MessagePack`]
+    #[error("get_with only supports Codec::MessagePack, store is configured with {0:?}")]
+    UnsupportedCodec(Codec),
 }
 
+/// Error serializing a value to an `rkyv` archive, wrapping whichever
+/// serializer/scratch-space/shared-pointer error `rkyv::to_bytes` produced
+#[derive(thiserror::Error, Debug)]
+#[error("rkyv serialization error: {0}")]
+pub struct RkyvSerializeError(String);
+
 /// Errors that can occur during `PkvStore::set`
 #[derive(thiserror::Error, Debug)]
 pub enum SetError {
@@ -51,11 +81,18 @@ pub enum SetError {
     /// An internal table error from the `redb` crate
     #[error("ReDbTableError error")]
     ReDbTableError(#[from] redb::TableError),
-    /// Error when serializing the value
-    #[error("MessagePack serialization error")]
-    MessagePack(#[from] rmp_serde::encode::Error),
+    /// Error when encoding the value with the store's configured [`Codec`]
+    #[error("Codec encode error")]
+    Codec(#[from] CodecError),
     #[error("KeyConversionError")]
     KeyConversion,
+    /// An internal database error from the `redb` crate, e.g. while creating
+    /// a backup file
+    #[error("ReDbDatabaseError error")]
+    ReDbDatabaseError(#[from] redb::DatabaseError),
+    /// Error serializing a value to an `rkyv` archive in [`ReDbStore::set_archived`]
+    #[error("rkyv serialization error")]
+    RkyvSerialize(#[from] RkyvSerializeError),
 }
 
 impl ReDbStore {
@@ -73,24 +110,438 @@ impl ReDbStore {
         write_txn.open_table(TABLE).unwrap();
         write_txn.commit().unwrap();
 
-        Self { db }
+        Self {
+            db,
+            codec: Codec::default(),
+            namespace_names: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Use `codec` instead of the default [`Codec::MessagePack`] to encode
+    /// and decode values in this store.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// The codec currently used to encode and decode values in this store
+    pub fn codec(&self) -> Codec {
+        self.codec
     }
 }
 
 const TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("redb");
 
+/// A staged batch of key/value writes that get committed together inside a
+/// single `redb` write transaction.
+///
+/// Building up a `WriteBatch` and calling [`ReDbStore::set_batch`] avoids
+/// paying for one commit per key, and guarantees all-or-nothing semantics:
+/// either every staged entry lands, or (if the transaction fails) none of
+/// them do.
+pub struct WriteBatch {
+    codec: Codec,
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl WriteBatch {
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Stage a serialized value for `key`. Nothing is written until the batch
+    /// is passed to [`ReDbStore::set_batch`].
+    pub fn set<T: Serialize>(
+        &mut self,
+        key: impl Into<String>,
+        value: &T,
+    ) -> Result<(), SetError> {
+        let bytes = self.codec.encode(value)?;
+        self.entries.push((key.into(), bytes));
+        Ok(())
+    }
+
+    /// Stage a string value for `key`.
+    pub fn set_string(&mut self, key: impl Into<String>, value: &str) -> Result<(), SetError> {
+        let bytes = self.codec.encode(&value)?;
+        self.entries.push((key.into(), bytes));
+        Ok(())
+    }
+}
+
+impl ReDbStore {
+    /// Start a [`WriteBatch`] using this store's configured codec.
+    pub fn batch(&self) -> WriteBatch {
+        WriteBatch::new(self.codec)
+    }
+
+    /// Commit every entry staged in `batch` inside a single write
+    /// transaction, so grouped settings (e.g. the sub-keys of a game save)
+    /// are written all-or-nothing instead of one commit per key.
+    pub fn set_batch(&mut self, batch: WriteBatch) -> Result<(), SetError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(TABLE)?;
+            for (key, bytes) in &batch.entries {
+                table.insert(key.as_str(), bytes.as_slice())?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+impl ReDbStore {
+    /// Snapshot every key in the store — including every [`Namespace`]'s own
+    /// table, not just the default one — into a freshly created `redb`
+    /// database at `dest`, so a game can later [`restore`](Self::restore)
+    /// from it (e.g. for cloud-save / "export profile" features) without
+    /// reaching into the live `bevy_pkv.redb` file directly.
+    ///
+    /// This is only available on [`ReDbStore`], not the other backends:
+    /// `redb`'s backup file is a snapshot of its own on-disk table format,
+    /// which `FSStore`'s flat per-key files and `LocalStorageStore`'s
+    /// browser `LocalStorage` entries have no equivalent of.
+    pub fn backup(&self, dest: &Path) -> Result<(), SetError> {
+        let backup_db = Database::create(dest)?;
+
+        let read_txn = self.db.begin_read()?;
+        let write_txn = backup_db.begin_write()?;
+        {
+            for handle in read_txn.list_tables()? {
+                let table: TableDefinition<&str, &[u8]> = TableDefinition::new(handle.name());
+                let src_table = read_txn.open_table(table)?;
+                let mut dest_table = write_txn.open_table(table)?;
+                for entry in src_table.iter()? {
+                    let (key, value) = entry?;
+                    dest_table.insert(key.value(), value.value())?;
+                }
+            }
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Replace the contents of the store — every table, including every
+    /// [`Namespace`]'s own table — with the entries from a backup file
+    /// previously written by [`backup`](Self::backup). Tables that exist in
+    /// this store but not in the backup are dropped, so the store exactly
+    /// matches the backup afterwards instead of keeping stale leftover
+    /// tables the backup doesn't know about.
+    ///
+    /// Only available on [`ReDbStore`]; see [`backup`](Self::backup).
+    pub fn restore(&mut self, src: &Path) -> Result<(), SetError> {
+        let backup_db = Database::open(src)?;
+        let read_txn = backup_db.begin_read()?;
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let existing_tables: Vec<String> = write_txn
+                .list_tables()?
+                .map(|handle| handle.name().to_string())
+                .collect();
+            for name in &existing_tables {
+                let table: TableDefinition<&str, &[u8]> = TableDefinition::new(name);
+                write_txn.delete_table(table)?;
+            }
+
+            for handle in read_txn.list_tables()? {
+                let table: TableDefinition<&str, &[u8]> = TableDefinition::new(handle.name());
+                let src_table = read_txn.open_table(table)?;
+                let mut dest_table = write_txn.open_table(table)?;
+                for entry in src_table.iter()? {
+                    let (key, value) = entry?;
+                    dest_table.insert(key.value(), value.value())?;
+                }
+            }
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+}
+
+/// The table archived values are stored in, separate from the default
+/// [`TABLE`] codec-encoded values live in. `get`, `range`, and `backup`/
+/// `restore` all read the default table's bytes straight through
+/// [`Codec::decode`] (or, for backup/restore, copy them byte-for-byte
+/// without caring what they are); mixing raw `rkyv` archive bytes into that
+/// same table would make `get`/`range` fail decoding them and corrupt
+/// `backup`/`restore`'s byte-for-byte copy of a table that's supposed to be
+/// all one codec. A separate table sidesteps that, and [`backup`]/[`restore`]
+/// still pick it up for free since they iterate every table in the database
+/// rather than just [`TABLE`].
+///
+/// [`backup`]: ReDbStore::backup
+/// [`restore`]: ReDbStore::restore
+const ARCHIVED_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("redb_archived");
+
+/// A validated view of an archived value returned by
+/// [`ReDbStore::get_archived`].
+///
+/// This is not a zero-copy view: `redb::AccessGuard::value()` returns bytes
+/// at an arbitrary offset into the database's page storage, with no
+/// guarantee they're aligned to `T::Archived`'s alignment, but `rkyv`'s
+/// archived-root access requires an aligned buffer — dereferencing straight
+/// off the guard's bytes would be undefined behavior whenever the stored
+/// bytes happen to land at a misaligned offset. [`get_archived`] copies the
+/// guard's bytes into an owned [`rkyv::AlignedVec`] before validating them,
+/// trading the allocation-free read this was originally meant to provide for
+/// a reference that's actually sound to dereference.
+///
+/// [`get_archived`]: ReDbStore::get_archived
+pub struct ArchivedValue<T: Archive> {
+    bytes: rkyv::AlignedVec,
+    _value: std::marker::PhantomData<T>,
+}
+
+impl<T> Deref for ArchivedValue<T>
+where
+    T: Archive,
+    T::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+{
+    type Target = T::Archived;
+
+    fn deref(&self) -> &Self::Target {
+        // Already validated once in `get_archived`, so the unchecked access
+        // here just re-derives the reference from `bytes`, which is already
+        // aligned since it's an `AlignedVec`.
+        unsafe { rkyv::archived_root::<T>(&self.bytes) }
+    }
+}
+
+impl ReDbStore {
+    /// Serialize `value` as an `rkyv` archive and store the raw archive
+    /// bytes under `key` in a table separate from the store's regular
+    /// codec-encoded keys, bypassing the store's configured [`Codec`]. Read
+    /// it back with [`get_archived`](Self::get_archived), not [`Self::get`]
+    /// (which only ever looks in the regular table).
+    pub fn set_archived<T>(&mut self, key: &str, value: &T) -> Result<(), SetError>
+    where
+        T: rkyv::Serialize<rkyv::ser::serializers::AllocSerializer<256>>,
+    {
+        let bytes = rkyv::to_bytes::<T, 256>(value)
+            .map_err(|err| RkyvSerializeError(err.to_string()))?;
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(ARCHIVED_TABLE)?;
+            table.insert(key, bytes.as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Read the value for `key` as a validated [`ArchivedValue`], instead of
+    /// fully deserializing it through [`Codec::decode`]. This is intended for
+    /// large, read-hot values (loaded tables, level data) where most reads
+    /// only touch a few fields, so paying for full deserialization on every
+    /// read is wasteful.
+    ///
+    /// `T` must have been written with [`Self::set_archived`], not
+    /// [`Self::set`] (which writes to a different table entirely).
+    pub fn get_archived<T>(&self, key: &str) -> Result<ArchivedValue<T>, GetError>
+    where
+        T: Archive,
+        T::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+    {
+        let read_txn = self.db.begin_read()?;
+        let table = match read_txn.open_table(ARCHIVED_TABLE) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Err(GetError::NotFound),
+            Err(err) => return Err(err.into()),
+        };
+        let guard = table.get(key)?.ok_or(GetError::NotFound)?;
+        let mut bytes = rkyv::AlignedVec::with_capacity(guard.value().len());
+        bytes.extend_from_slice(guard.value());
+        rkyv::check_archived_root::<T>(&bytes).map_err(|_| GetError::RkyvValidation)?;
+        Ok(ArchivedValue {
+            bytes,
+            _value: std::marker::PhantomData,
+        })
+    }
+}
+
+/// A handle to one of a store's named sub-stores, each backed by its own
+/// `redb` table. Obtained from [`ReDbStore::namespace`]; lets a game keep
+/// e.g. `"settings"`, `"saves"`, and `"leaderboard"` in logically separate
+/// tables instead of sharing the store's single flat key space.
+///
+/// Namespace names are leaked to `'static` the first time [`ReDbStore::namespace`]
+/// sees them, since `redb::TableDefinition` requires a `'static` name; the
+/// store interns each leaked name so repeat calls with the same name reuse
+/// it instead of leaking again, but the set of distinct names ever passed to
+/// `namespace` still grows for the life of the store, so callers should use
+/// a small, reused set of namespace names rather than one per entity.
+pub struct Namespace<'a> {
+    db: &'a Database,
+    codec: Codec,
+    table: TableDefinition<'static, &'static str, &'static [u8]>,
+}
+
+impl<'a> Namespace<'a> {
+    /// Serialize and store the value under `key` in this namespace
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<(), SetError> {
+        let bytes = self.codec.encode(value)?;
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table)?;
+            table.insert(key, bytes.as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Get the value for `key` in this namespace. Returns
+    /// `Err(GetError::NotFound)` if the key does not exist.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, GetError> {
+        let read_txn = self.db.begin_read()?;
+        let table = match read_txn.open_table(self.table) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Err(GetError::NotFound),
+            Err(err) => return Err(err.into()),
+        };
+        let guard = table.get(key)?.ok_or(GetError::NotFound)?;
+        self.codec.decode(guard.value()).map_err(Into::into)
+    }
+
+    /// Append `value` to the list of values stored under `key`, keeping
+    /// every previously appended value in insertion order. Use
+    /// [`get_all`](Self::get_all) to read them back.
+    pub fn append<T: Serialize + DeserializeOwned>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), SetError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table)?;
+            let mut items: Vec<Vec<u8>> = match table.get(key)? {
+                Some(guard) => self.codec.decode(guard.value())?,
+                None => Vec::new(),
+            };
+            items.push(self.codec.encode(value)?);
+            let bytes = self.codec.encode(&items)?;
+            table.insert(key, bytes.as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Read back every value appended under `key` via [`append`](Self::append),
+    /// in insertion order. Returns an empty `Vec` if `key` has never been
+    /// appended to.
+    pub fn get_all<T: DeserializeOwned>(&self, key: &str) -> Result<Vec<T>, GetError> {
+        let read_txn = self.db.begin_read()?;
+        let table = match read_txn.open_table(self.table) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let items: Vec<Vec<u8>> = match table.get(key)? {
+            Some(guard) => self.codec.decode(guard.value())?,
+            None => return Ok(Vec::new()),
+        };
+        items
+            .iter()
+            .map(|bytes| self.codec.decode(bytes).map_err(Into::into))
+            .collect()
+    }
+}
+
+impl ReDbStore {
+    /// Open a handle to the named sub-store, creating its backing table on
+    /// first write if it doesn't exist yet. See [`Namespace`].
+    pub fn namespace(&self, name: &str) -> Namespace<'_> {
+        let mut names = self.namespace_names.lock().unwrap();
+        let name: &'static str = match names.get(name) {
+            Some(name) => name,
+            None => {
+                let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+                names.insert(name);
+                name
+            }
+        };
+        Namespace {
+            db: &self.db,
+            codec: self.codec,
+            table: TableDefinition::new(name),
+        }
+    }
+}
+
+impl ReDbStore {
+    /// Every key in the store whose name begins with `prefix`, in sorted
+    /// key order. Backed by `redb`'s native ordered range query, so unlike
+    /// [`keys`](StoreImpl::keys) only the matching keys are read from the
+    /// table.
+    pub fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>, GetError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let mut keys = Vec::new();
+        for entry in table.range(prefix..)? {
+            let (key, _) = entry?;
+            let key = key.value();
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if key != SCHEMA_VERSION_KEY {
+                keys.push(key.to_string());
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Every `(key, value)` pair whose key falls in `range`, decoded with
+    /// the store's configured codec, in sorted key order. Backed by
+    /// `redb`'s native ordered range query, so keys outside `range` are
+    /// never read, unlike loading every key via [`keys`](StoreImpl::keys)
+    /// and filtering afterwards.
+    pub fn range<T: DeserializeOwned>(
+        &self,
+        range: impl std::ops::RangeBounds<String>,
+    ) -> Result<Vec<(String, T)>, GetError> {
+        let start = match range.start_bound() {
+            std::ops::Bound::Included(k) => std::ops::Bound::Included(k.as_str()),
+            std::ops::Bound::Excluded(k) => std::ops::Bound::Excluded(k.as_str()),
+            std::ops::Bound::Unbounded => std::ops::Bound::Unbounded,
+        };
+        let end = match range.end_bound() {
+            std::ops::Bound::Included(k) => std::ops::Bound::Included(k.as_str()),
+            std::ops::Bound::Excluded(k) => std::ops::Bound::Excluded(k.as_str()),
+            std::ops::Bound::Unbounded => std::ops::Bound::Unbounded,
+        };
+
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(TABLE)?;
+        let mut results = Vec::new();
+        for entry in table.range::<&str>((start, end))? {
+            let (key, value) = entry?;
+            let key = key.value();
+            if key == SCHEMA_VERSION_KEY {
+                continue;
+            }
+            let decoded = self.codec.decode(value.value())?;
+            results.push((key.to_string(), decoded));
+        }
+        Ok(results)
+    }
+}
+
 impl StoreImpl for ReDbStore {
     type GetError = GetError;
     type SetError = SetError;
 
     /// Serialize and store the value
     fn set<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), Self::SetError> {
-        let mut serializer = rmp_serde::Serializer::new(Vec::new()).with_struct_map();
-        value.serialize(&mut serializer)?;
+        let bytes = self.codec.encode(value)?;
         let write_txn = self.db.begin_write()?;
         {
             let mut table = write_txn.open_table(TABLE).unwrap();
-            table.insert(key, serializer.into_inner().as_slice())?;
+            table.insert(key, bytes.as_slice())?;
         }
         write_txn.commit()?;
 
@@ -99,7 +550,7 @@ impl StoreImpl for ReDbStore {
 
     /// More or less the same as set::<String>, but can take a &str
     fn set_string(&mut self, key: &str, value: &str) -> Result<(), Self::SetError> {
-        let bytes = rmp_serde::to_vec(value)?;
+        let bytes = self.codec.encode(&value)?;
         let write_txn = self.db.begin_write()?;
         {
             let mut table = write_txn.open_table(TABLE).unwrap();
@@ -117,7 +568,7 @@ impl StoreImpl for ReDbStore {
         let table = read_txn.open_table(TABLE)?;
         let key = table.get(key)?.ok_or(Self::GetError::NotFound)?;
         let bytes = key.value();
-        let value = rmp_serde::from_slice(bytes)?;
+        let value = self.codec.decode(bytes)?;
         Ok(value)
     }
 
@@ -126,12 +577,28 @@ impl StoreImpl for ReDbStore {
         key: &str,
         seed: T,
     ) -> Result<<T as DeserializeSeed<'_>>::Value, Self::GetError> {
+        // This can't delegate to `Codec::decode_seed` the way `get` delegates
+        // to `Codec::decode`: going through that extra generic function call
+        // forces rustc to prove `<T as DeserializeSeed<'de>>::Value` (for
+        // `decode_seed`'s own local `'de`, tied to `bytes`/`key` below) equal
+        // to `<T as DeserializeSeed<'_>>::Value` (what this method's return
+        // type, tied to `&self`, actually requires) — a `for<'de>` bound
+        // alone doesn't assert that equality, so the call is rejected as
+        // returning a value that could reference the dropped-at-end-of-method
+        // `key`. Matching on `self.codec` inline, in the same generic body
+        // that produces the return value, sidesteps the extra boundary and
+        // lets rustc unify the lifetime directly instead.
+        if self.codec != Codec::MessagePack {
+            return Err(Self::GetError::UnsupportedCodec(self.codec));
+        }
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(TABLE)?;
         let key = table.get(key)?.ok_or(Self::GetError::NotFound)?;
         let bytes = key.value();
         let mut deserializer = rmp_serde::decode::Deserializer::new(bytes);
-        seed.deserialize(&mut deserializer).map_err(Into::into)
+        seed.deserialize(&mut deserializer)
+            .map_err(CodecError::from)
+            .map_err(Into::into)
     }
 
     /// Clear all keys and their values
@@ -158,7 +625,10 @@ impl StoreImpl for ReDbStore {
             let read_txn = self.db.begin_read()?;
             let table = read_txn.open_table(TABLE)?;
             let range = table.iter()?;
-            range.map(|r| r.unwrap().0.value().to_string()).collect()
+            range
+                .map(|r| r.unwrap().0.value().to_string())
+                .filter(|key| key != SCHEMA_VERSION_KEY)
+                .collect()
         };
 
         Ok(keys)